@@ -9,6 +9,12 @@
 //! Do not use this for cryptography.
 //!
 //! https://code.google.com/p/xxhash/
+//!
+//! Builds `no_std` when the `std` feature (on by default) is turned off.
+//! The `xxh3`/`xxh128` modules (which buffer input in a `Vec`), the
+//! `stable` module and the randomized-seed `BuildHasher` (which needs OS
+//! thread-locals) are `std`-only. Enable `unsafe-opt` for the original
+//! raw-pointer-tuned `XXHasher` loop.
 
 
 
@@ -20,17 +26,32 @@
 
 #![crate_name="xxhash"]
 #![crate_type="lib"]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#![allow(unused_assignments, unused_variables)] // `read_ptr!`
+#![allow(unused_assignments, unused_variables)]
 
 
 // unstable
 //#[cfg(test)]
 // extern crate test;
 
-use std::mem::{uninitialized, transmute};
-use std::ptr::{copy};
-use std::hash::{Hash, Hasher};
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+
+#[cfg(feature = "digest")]
+extern crate digest;
+
+#[cfg(feature = "unsafe-opt")]
+use std::mem::transmute;
+#[cfg(feature = "unsafe-opt")]
+use std::ptr::copy;
+#[cfg(feature = "std")]
+use std::cell::Cell;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::default::Default;
 use std::num::Wrapping;
 use std::ops::{Shl, Shr, BitOr};
@@ -38,8 +59,12 @@ use std::ops::{Shl, Shr, BitOr};
 //unstable
 //#[cfg(test)] use test::Bencher;
 
-pub mod macros;
-pub mod xxh32;
+#[cfg(feature = "std")]
+pub mod xxh3;
+#[cfg(feature = "std")]
+pub mod xxh128;
+#[cfg(feature = "std")]
+pub mod stable;
 
 // large prime, new_with_seed(0) is so boring
 const HAPPY_SEED: u64 = 18446744073709551557_u64;
@@ -62,7 +87,7 @@ pub fn oneshot(input: &[u8], seed: u64) -> u64 { #![inline]
 
 #[derive(Copy)]
 pub struct XXHasher {
-    memory: [u64; 4],
+    buffer: [u8; 32],
     v1: u64,
     v2: u64,
     v3: u64,
@@ -76,8 +101,16 @@ impl XXHasher {
     /// Unless testing, randomize the seed for each set of
     /// hashes, e.g. when creating a new `HashMap`.
     pub fn new_with_seed(seed: u64) -> XXHasher { #![inline]
-        let mut state: XXHasher = unsafe { uninitialized() };
-        state.seed = seed;
+        let mut state = XXHasher {
+            buffer: [0u8; 32],
+            v1: 0,
+            v2: 0,
+            v3: 0,
+            v4: 0,
+            total_len: 0,
+            seed: seed,
+            memsize: 0,
+        };
         state.reset();
         state
     }
@@ -97,6 +130,176 @@ impl XXHasher {
     }
 }
 
+// The two `Hasher` impls below differ only in how they read the 8 and
+// 4-byte words out of `buffer`/`input`, and compute the same digest on
+// little-endian targets (the `unsafe-opt` path reads words in native byte
+// order).
+//
+// The default path reads words through `u64::from_le_bytes`/
+// `u32::from_le_bytes` on ordinary slices: no raw pointers, no
+// `transmute`, nothing that needs `unsafe`. That's what makes `XXHasher`
+// usable in `no_std` / WASM builds. `new_with_seed`, shared by both paths,
+// zero-fills `buffer` so it never goes through `mem::uninitialized()`.
+//
+// The `unsafe-opt` feature switches to the original ILP-tuned version,
+// which detaches the four accumulator lanes through raw pointer reads so
+// LLVM doesn't serialize them. Enable it if you've measured the
+// difference mattering for your workload.
+
+#[cfg(not(feature = "unsafe-opt"))]
+fn read_le_u64(buf: &[u8]) -> u64 { #![inline(always)]
+    u64::from_le_bytes([buf[0], buf[1], buf[2], buf[3], buf[4], buf[5], buf[6], buf[7]])
+}
+
+#[cfg(not(feature = "unsafe-opt"))]
+fn read_le_u32(buf: &[u8]) -> u32 { #![inline(always)]
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+#[cfg(not(feature = "unsafe-opt"))]
+impl Hasher for XXHasher {
+
+    /// Compute the hash. This can be used for intermediate values too.
+    fn finish(&self) -> u64 { #![inline]
+        let mut rem = self.memsize;
+        let mut h64: Wrapping<u64> = if self.total_len < 32 {
+            Wrapping(self.seed) + PRIME5
+        } else {
+            let v1: Wrapping<u64> = Wrapping(self.v1);
+            let v2: Wrapping<u64> = Wrapping(self.v2);
+            let v3: Wrapping<u64> = Wrapping(self.v3);
+            let v4: Wrapping<u64> = Wrapping(self.v4);
+
+            let mut h = rotl64(v1, 1) + rotl64(v2, 7) + rotl64(v3, 12) + rotl64(v4, 18);
+
+            macro_rules! permute(($v: ident) => ({
+                let mut v = $v;
+                v = v * PRIME2; v = rotl64(v, 31); v = v * PRIME1; h = h ^ v; h = h * PRIME1 + PRIME4;
+            }));
+            // this step does not exist in xxh32
+            permute!(v1); permute!(v2); permute!(v3); permute!(v4);
+
+            h
+        };
+
+        h64 = h64 + Wrapping(self.total_len as u64);
+
+        let mut pos = 0;
+        while rem >= 8 {
+            let mut k1: Wrapping<u64> = Wrapping(read_le_u64(&self.buffer[pos..pos + 8])) * PRIME2;
+            k1 = rotl64(k1, 31); k1 = k1 * PRIME1;
+            h64 = h64 ^ k1;
+            h64 = rotl64(h64, 27) * PRIME1 + PRIME4;
+            pos += 8; rem -= 8;
+        }
+
+        if rem >= 4 {
+            h64 = h64 ^ (Wrapping(read_le_u32(&self.buffer[pos..pos + 4]) as u64) * PRIME1);
+            h64 = rotl64(h64, 23) * PRIME2 + PRIME3;
+            pos += 4; rem -= 4;
+        }
+
+        while rem > 0 {
+            h64 = h64 ^ (Wrapping(self.buffer[pos] as u64) * PRIME5);
+            h64 = rotl64(h64, 11) * PRIME1;
+            pos += 1; rem -= 1;
+        }
+
+        h64 = h64.clone() ^ (h64.clone() >> 33);
+        h64 = h64 * PRIME2;
+        h64 = h64.clone() ^ (h64.clone() >> 29);
+        h64 = h64 * PRIME3;
+        h64 = h64.clone() ^ (h64.clone() >> 32);
+
+        h64.0
+    }
+
+    /// This is where you feed your data in.
+    fn write(&mut self, input: &[u8]) {
+        let mut rem: usize = input.len();
+        let mut data: &[u8] = input;
+
+        self.total_len += rem as u64;
+
+        // not enough data for one 32-byte chunk,
+        // so just fill the buffer and return.
+        if self.memsize + rem < 32 {
+            self.buffer[self.memsize..self.memsize + rem].copy_from_slice(data);
+            self.memsize += rem;
+            return;
+        }
+
+        // some data left from previous update
+        // fill the buffer and eat it
+        if self.memsize != 0 {
+            let bump: usize = 32 - self.memsize;
+            self.buffer[self.memsize..32].copy_from_slice(&data[..bump]);
+
+            let mut v1: Wrapping<u64> = Wrapping(self.v1);
+            let mut v2: Wrapping<u64> = Wrapping(self.v2);
+            let mut v3: Wrapping<u64> = Wrapping(self.v3);
+            let mut v4: Wrapping<u64> = Wrapping(self.v4);
+
+            macro_rules! eat(($v: ident, $off: expr) => ({
+                $v = $v + Wrapping(read_le_u64(&self.buffer[$off..$off + 8])) * PRIME2;
+                $v = rotl64($v, 31); $v = $v * PRIME1;
+            }));
+
+            eat!(v1, 0); eat!(v2, 8); eat!(v3, 16); eat!(v4, 24);
+
+            self.v1 = v1.0;
+            self.v2 = v2.0;
+            self.v3 = v3.0;
+            self.v4 = v4.0;
+
+            data = &data[bump..];
+            rem -= bump;
+            self.memsize = 0;
+        }
+
+        {
+            let mut v1: Wrapping<u64> = Wrapping(self.v1);
+            let mut v2: Wrapping<u64> = Wrapping(self.v2);
+            let mut v3: Wrapping<u64> = Wrapping(self.v3);
+            let mut v4: Wrapping<u64> = Wrapping(self.v4);
+
+            macro_rules! eat(($v: ident, $off: expr) => ({
+                $v = $v + Wrapping(read_le_u64(&data[$off..$off + 8])) * PRIME2;
+                $v = rotl64($v, 31); $v = $v * PRIME1;
+            }));
+
+            // the main loop: eat whole chunks
+            while rem >= 32 {
+                eat!(v1, 0); eat!(v2, 8); eat!(v3, 16); eat!(v4, 24);
+                data = &data[32..];
+                rem -= 32;
+            }
+
+            self.v1 = v1.0;
+            self.v2 = v2.0;
+            self.v3 = v3.0;
+            self.v4 = v4.0;
+        }
+
+        // we have data left, so save it
+        if rem > 0 {
+            self.buffer[..rem].copy_from_slice(data);
+            self.memsize = rem;
+        }
+    }
+}
+
+// reads one unaligned, native-endian `$size` word at `$p`, then advances
+// `$p` past it and counts it off `$rem`.
+#[cfg(feature = "unsafe-opt")]
+macro_rules! read_ptr(($p: ident, $rem: ident, $size: ty) => ({
+    let word = ($p as *const $size).read_unaligned();
+    $p = $p.offset(::std::mem::size_of::<$size>() as isize);
+    $rem -= ::std::mem::size_of::<$size>();
+    word
+}));
+
+#[cfg(feature = "unsafe-opt")]
 impl Hasher for XXHasher {
 
     /// Compute the hash. This can be used for intermediate values too.
@@ -123,7 +326,7 @@ impl Hasher for XXHasher {
         };
 
         // and now we eat all the remaining bytes.
-        let mut p: *const u8 = transmute(&self.memory);
+        let mut p: *const u8 = transmute(&self.buffer);
         macro_rules! read(($size:ty) => (Wrapping(read_ptr!(p, rem, $size) as u64)));
 
         h64 = h64 + Wrapping(self.total_len as u64);
@@ -155,7 +358,7 @@ impl Hasher for XXHasher {
 
     /// This is where you feed your data in.
     fn write(&mut self, input: &[u8]) { unsafe {
-        let mem: *mut u8 = transmute(&self.memory);
+        let mem: *mut u8 = transmute(&self.buffer);
         let mut rem: usize = input.len();
         let mut data: *const u8 = input.as_ptr();
 
@@ -254,6 +457,43 @@ impl Default for XXHasher {
     }
 }
 
+/// `digest::Digest` integration, so `XXHasher` drops into anything
+/// generic over RustCrypto's `digest` crate (`XXHasher::new().chain_update(..).finalize()`
+/// and friends). Gated behind the `digest` feature since it's an
+/// optional dependency most users of this crate don't need.
+#[cfg(feature = "digest")]
+mod digest_impl {
+    use super::XXHasher;
+    use digest::consts::U8;
+    use digest::generic_array::GenericArray;
+    use digest::{FixedOutput, HashMarker, OutputSizeUser, Reset, Update};
+    use std::hash::Hasher;
+
+    impl HashMarker for XXHasher {}
+
+    impl Update for XXHasher {
+        fn update(&mut self, data: &[u8]) { #![inline]
+            self.write(data);
+        }
+    }
+
+    impl OutputSizeUser for XXHasher {
+        type OutputSize = U8;
+    }
+
+    impl FixedOutput for XXHasher {
+        fn finalize_into(self, out: &mut GenericArray<u8, U8>) {
+            out.copy_from_slice(&self.finish().to_be_bytes());
+        }
+    }
+
+    impl Reset for XXHasher {
+        fn reset(&mut self) { #![inline]
+            XXHasher::reset(self);
+        }
+    }
+}
+
 pub fn hash<T: ?Sized + Hash>(value: &T) -> u64
 {
     let mut state = XXHasher::new();
@@ -267,8 +507,93 @@ pub fn hash_with_seed<T: ?Sized + Hash>(seed: u64, value: &T) -> u64 { #![inline
     state.finish()
 }
 
+/// `BuildHasher` with a fixed seed. Every `XXHasher` it builds hashes the
+/// same way, which is what you want for a reproducible on-disk format or
+/// a test fixture, but *not* what you want for a `HashMap` exposed to
+/// attacker-controlled keys - see `RandomXxHashBuilder` for that.
+#[derive(Clone)]
+pub struct XxHashBuilder(u64);
+
+impl XxHashBuilder {
+    pub fn new(seed: u64) -> XxHashBuilder { #![inline]
+        XxHashBuilder(seed)
+    }
+}
+
+impl BuildHasher for XxHashBuilder {
+    type Hasher = XXHasher;
+
+    fn build_hasher(&self) -> XXHasher { #![inline]
+        XXHasher::new_with_seed(self.0)
+    }
+}
+
+impl Default for XxHashBuilder {
+    fn default() -> XxHashBuilder { #![inline]
+        XxHashBuilder(HAPPY_SEED)
+    }
+}
+
+// draws a fresh seed per `RandomXxHashBuilder`, mixing a thread-local
+// counter through `RandomState`'s own OS-seeded entropy so the sequence
+// of seeds isn't predictable from one instance to the next. Needs a real
+// thread-local and `std`'s `RandomState`, so this whole randomized
+// builder is `std`-only; `no_std` users needing a `HashMap` hasher reach
+// for `XxHashBuilder` with a seed of their own choosing instead.
+#[cfg(feature = "std")]
+fn next_seed() -> u64 {
+    thread_local! {
+        static COUNTER: Cell<u64> = Cell::new(0);
+    }
+    COUNTER.with(|counter| {
+        let n = counter.get();
+        counter.set(n.wrapping_add(1));
+        let mut entropy = RandomState::new().build_hasher();
+        n.hash(&mut entropy);
+        entropy.finish()
+    })
+}
+
+/// `BuildHasher` with a randomized per-instance seed, for use as a
+/// `HashMap` hasher. Unless you specifically need reproducible hashes,
+/// prefer this over `XxHashBuilder`: a predictable seed lets an attacker
+/// who controls map keys engineer hash flooding, the same attack
+/// `std`'s SipHash-keyed `RandomState` exists to prevent.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct RandomXxHashBuilder(u64);
+
+#[cfg(feature = "std")]
+impl RandomXxHashBuilder {
+    pub fn new() -> RandomXxHashBuilder { #![inline]
+        RandomXxHashBuilder(next_seed())
+    }
+}
+
+#[cfg(feature = "std")]
+impl BuildHasher for RandomXxHashBuilder {
+    type Hasher = XXHasher;
+
+    fn build_hasher(&self) -> XXHasher { #![inline]
+        XXHasher::new_with_seed(self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for RandomXxHashBuilder {
+    fn default() -> RandomXxHashBuilder { #![inline]
+        RandomXxHashBuilder::new()
+    }
+}
+
+/// A `HashMap` keyed by `XXHasher` with a randomized seed, analogous to
+/// the standard library's default `HashMap<K, V>` but using xxHash
+/// instead of SipHash.
+#[cfg(feature = "std")]
+pub type XxHashMap<K, V> = HashMap<K, V, RandomXxHashBuilder>;
+
 /// the official sanity test
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 fn test_base<F>(f: F) where F: Fn(&[u8], u64) -> u64 {
     static BUFSIZE: usize = 101;
     static PRIME: u32 = 2654435761;
@@ -311,6 +636,7 @@ fn bench_base<F>(bench: &mut Bencher, f: F )
 }*/
 
 #[test]
+#[cfg(feature = "std")]
 fn test_oneshot() {
     test_base(|v, seed|{
         let mut state = XXHasher::new_with_seed(seed);
@@ -320,6 +646,7 @@ fn test_oneshot() {
 }
 
 #[test]
+#[cfg(feature = "std")]
 fn test_chunks() {
     test_base(|v, seed|{
         let mut state = XXHasher::new_with_seed(seed);
@@ -419,6 +746,39 @@ fn test_hash_no_concat_alias() {
     assert!(hash(&v) != hash(&w));
 }
 
+#[test]
+fn test_fixed_build_hasher_is_reproducible() {
+    let builder = XxHashBuilder::new(42);
+    let mut a = builder.build_hasher();
+    let mut b = builder.build_hasher();
+    "some key".hash(&mut a);
+    "some key".hash(&mut b);
+    assert_eq!(a.finish(), b.finish());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_random_build_hasher_varies() {
+    let a = RandomXxHashBuilder::new();
+    let b = RandomXxHashBuilder::new();
+    let mut ha = a.build_hasher();
+    let mut hb = b.build_hasher();
+    "some key".hash(&mut ha);
+    "some key".hash(&mut hb);
+    assert!(ha.finish() != hb.finish());
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_xx_hash_map() {
+    let mut map: XxHashMap<&str, u32> = XxHashMap::default();
+    map.insert("one", 1);
+    map.insert("two", 2);
+    assert_eq!(map.get("one"), Some(&1));
+    assert_eq!(map.get("two"), Some(&2));
+    assert_eq!(map.get("three"), None);
+}
+
 // unstable
 /*#[bench]
 fn bench_str_under_8_bytes(b: &mut Bencher) {
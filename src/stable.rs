@@ -0,0 +1,358 @@
+//! Endianness-independent ("stable") xxh64 hashing.
+//!
+//! `XXHasher` takes typed values through the `Hasher` trait's default
+//! `write_uN`/`write_usize` methods, which feed it native-endian bytes
+//! (and under the `unsafe-opt` feature it also reads its words in native
+//! byte order), so its digests depend on the host: the same value hashes
+//! differently on big- and little-endian machines, and a `usize` hashes
+//! differently on 32- vs 64-bit targets (see `test_hash_usize` in the
+//! crate root). That's fine
+//! for in-memory structures like `HashMap`, but it's useless for
+//! anything persisted across machines or process runs - on-disk caches,
+//! incremental-compilation-style fingerprints, content addressing.
+//!
+//! `StableXxHasher` is the same xxh64 algorithm with every multi-byte
+//! read pinned to little-endian, and `usize`/`isize` normalized to a
+//! fixed 64 bits before they're folded in. Identical byte streams and
+//! identical typed values then hash identically everywhere.
+
+use std::hash::Hasher;
+use std::num::Wrapping;
+
+use {rotl64, HAPPY_SEED, PRIME1, PRIME2, PRIME3, PRIME4, PRIME5};
+
+fn read_le64(buf: &[u8]) -> Wrapping<u64> {
+    Wrapping(
+        (buf[0] as u64)
+            | (buf[1] as u64) << 8
+            | (buf[2] as u64) << 16
+            | (buf[3] as u64) << 24
+            | (buf[4] as u64) << 32
+            | (buf[5] as u64) << 40
+            | (buf[6] as u64) << 48
+            | (buf[7] as u64) << 56,
+    )
+}
+
+fn read_le32(buf: &[u8]) -> Wrapping<u64> {
+    Wrapping(((buf[0] as u32) | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24) as u64)
+}
+
+pub struct StableXxHasher {
+    buffer: [u8; 32],
+    buffered: usize,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    v4: u64,
+    total_len: u64,
+    seed: u64,
+}
+
+impl StableXxHasher {
+    pub fn new() -> StableXxHasher { #![inline]
+        StableXxHasher::new_with_seed(HAPPY_SEED)
+    }
+
+    pub fn new_with_seed(seed: u64) -> StableXxHasher { #![inline]
+        let mut state = StableXxHasher {
+            buffer: [0; 32],
+            buffered: 0,
+            v1: 0,
+            v2: 0,
+            v3: 0,
+            v4: 0,
+            total_len: 0,
+            seed: seed,
+        };
+        state.reset();
+        state
+    }
+
+    fn reset(&mut self) { #![inline]
+        self.v1 = (Wrapping(self.seed) + PRIME1 + PRIME2).0;
+        self.v2 = (Wrapping(self.seed) + PRIME2).0;
+        self.v3 = self.seed;
+        self.v4 = (Wrapping(self.seed) - PRIME1).0;
+        self.total_len = 0;
+        self.buffered = 0;
+    }
+}
+
+macro_rules! eat(($v: ident, $word: expr) => ({
+    $v = $v + $word * PRIME2; $v = Wrapping(rotl64($v.0, 31)); $v = $v * PRIME1;
+}));
+
+impl Hasher for StableXxHasher {
+    fn finish(&self) -> u64 {
+        let mut rem = self.buffered;
+        let mut h64: Wrapping<u64> = if self.total_len < 32 {
+            Wrapping(self.seed) + PRIME5
+        } else {
+            let v1 = Wrapping(self.v1);
+            let v2 = Wrapping(self.v2);
+            let v3 = Wrapping(self.v3);
+            let v4 = Wrapping(self.v4);
+
+            let mut h = Wrapping(rotl64(v1.0, 1)) + Wrapping(rotl64(v2.0, 7))
+                + Wrapping(rotl64(v3.0, 12)) + Wrapping(rotl64(v4.0, 18));
+
+            macro_rules! permute(($v: ident) => ({
+                let mut v = $v;
+                v = v * PRIME2; v = Wrapping(rotl64(v.0, 31)); v = v * PRIME1;
+                h = h ^ v; h = h * PRIME1 + PRIME4;
+            }));
+            permute!(v1); permute!(v2); permute!(v3); permute!(v4);
+
+            h
+        };
+
+        h64 = h64 + Wrapping(self.total_len);
+
+        let mut pos = 0;
+        while rem >= 8 {
+            let mut k1 = read_le64(&self.buffer[pos..pos + 8]) * PRIME2;
+            k1 = Wrapping(rotl64(k1.0, 31));
+            k1 = k1 * PRIME1;
+            h64 = h64 ^ k1;
+            h64 = Wrapping(rotl64(h64.0, 27)) * PRIME1 + PRIME4;
+            pos += 8;
+            rem -= 8;
+        }
+
+        if rem >= 4 {
+            h64 = h64 ^ (read_le32(&self.buffer[pos..pos + 4]) * PRIME1);
+            h64 = Wrapping(rotl64(h64.0, 23)) * PRIME2 + PRIME3;
+            pos += 4;
+            rem -= 4;
+        }
+
+        while rem > 0 {
+            h64 = h64 ^ (Wrapping(self.buffer[pos] as u64) * PRIME5);
+            h64 = Wrapping(rotl64(h64.0, 11)) * PRIME1;
+            pos += 1;
+            rem -= 1;
+        }
+
+        h64 = h64 ^ Wrapping(h64.0 >> 33);
+        h64 = h64 * PRIME2;
+        h64 = h64 ^ Wrapping(h64.0 >> 29);
+        h64 = h64 * PRIME3;
+        h64 = h64 ^ Wrapping(h64.0 >> 32);
+
+        h64.0
+    }
+
+    fn write(&mut self, mut input: &[u8]) {
+        self.total_len += input.len() as u64;
+
+        if self.buffered + input.len() < 32 {
+            let start = self.buffered;
+            self.buffer[start..start + input.len()].copy_from_slice(input);
+            self.buffered += input.len();
+            return;
+        }
+
+        if self.buffered != 0 {
+            let bump = 32 - self.buffered;
+            self.buffer[self.buffered..32].copy_from_slice(&input[..bump]);
+
+            let mut v1 = Wrapping(self.v1);
+            let mut v2 = Wrapping(self.v2);
+            let mut v3 = Wrapping(self.v3);
+            let mut v4 = Wrapping(self.v4);
+
+            eat!(v1, read_le64(&self.buffer[0..8]));
+            eat!(v2, read_le64(&self.buffer[8..16]));
+            eat!(v3, read_le64(&self.buffer[16..24]));
+            eat!(v4, read_le64(&self.buffer[24..32]));
+
+            self.v1 = v1.0;
+            self.v2 = v2.0;
+            self.v3 = v3.0;
+            self.v4 = v4.0;
+
+            input = &input[bump..];
+            self.buffered = 0;
+        }
+
+        {
+            let mut v1 = Wrapping(self.v1);
+            let mut v2 = Wrapping(self.v2);
+            let mut v3 = Wrapping(self.v3);
+            let mut v4 = Wrapping(self.v4);
+
+            while input.len() >= 32 {
+                eat!(v1, read_le64(&input[0..8]));
+                eat!(v2, read_le64(&input[8..16]));
+                eat!(v3, read_le64(&input[16..24]));
+                eat!(v4, read_le64(&input[24..32]));
+                input = &input[32..];
+            }
+
+            self.v1 = v1.0;
+            self.v2 = v2.0;
+            self.v3 = v3.0;
+            self.v4 = v4.0;
+        }
+
+        if input.len() > 0 {
+            self.buffer[..input.len()].copy_from_slice(input);
+            self.buffered = input.len();
+        }
+    }
+
+    // the default `write_uN`/`write_iN` methods hash each value's
+    // native-endian bytes, which is exactly the non-portability this
+    // hasher exists to avoid. Route every fixed-width integer through an
+    // explicit little-endian encoding instead, and normalize the
+    // pointer-width types to 64 bits so a `usize` hashes the same on
+    // 32-bit and 64-bit targets.
+    fn write_u8(&mut self, i: u8) { #![inline] self.write(&[i]) }
+    fn write_u16(&mut self, i: u16) { #![inline]
+        self.write(&[i as u8, (i >> 8) as u8])
+    }
+    fn write_u32(&mut self, i: u32) { #![inline]
+        self.write(&[i as u8, (i >> 8) as u8, (i >> 16) as u8, (i >> 24) as u8])
+    }
+    fn write_u64(&mut self, i: u64) { #![inline]
+        let mut buf = [0u8; 8];
+        for n in 0..8 {
+            buf[n] = (i >> (8 * n)) as u8;
+        }
+        self.write(&buf)
+    }
+    fn write_u128(&mut self, i: u128) { #![inline]
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64)
+    }
+    fn write_usize(&mut self, i: usize) { #![inline]
+        self.write_u64(i as u64)
+    }
+    fn write_i8(&mut self, i: i8) { #![inline] self.write_u8(i as u8) }
+    fn write_i16(&mut self, i: i16) { #![inline] self.write_u16(i as u16) }
+    fn write_i32(&mut self, i: i32) { #![inline] self.write_u32(i as u32) }
+    fn write_i64(&mut self, i: i64) { #![inline] self.write_u64(i as u64) }
+    fn write_i128(&mut self, i: i128) { #![inline] self.write_u128(i as u128) }
+    fn write_isize(&mut self, i: isize) { #![inline] self.write_u64(i as i64 as u64) }
+}
+
+impl Clone for StableXxHasher {
+    fn clone(&self) -> StableXxHasher { #![inline]
+        StableXxHasher {
+            buffer: self.buffer,
+            buffered: self.buffered,
+            v1: self.v1,
+            v2: self.v2,
+            v3: self.v3,
+            v4: self.v4,
+            total_len: self.total_len,
+            seed: self.seed,
+        }
+    }
+}
+
+impl Default for StableXxHasher {
+    fn default() -> StableXxHasher { #![inline]
+        StableXxHasher::new()
+    }
+}
+
+pub fn oneshot(input: &[u8], seed: u64) -> u64 { #![inline]
+    let mut state = StableXxHasher::new_with_seed(seed);
+    state.write(input);
+    state.finish()
+}
+
+pub fn hash<T: ?Sized + ::std::hash::Hash>(value: &T) -> u64 {
+    let mut state = StableXxHasher::new();
+    value.hash(&mut state);
+    state.finish()
+}
+
+#[cfg(test)]
+fn byte_swap_words(buf: &[u8]) -> Vec<u8> {
+    // byte-swaps every 8-byte word of `buf`, so the result is what you'd
+    // get serializing the same logical u64 words with the opposite
+    // endianness.
+    buf.chunks(8)
+        .flat_map(|chunk| {
+            let mut swapped = chunk.to_vec();
+            swapped.reverse();
+            swapped
+        })
+        .collect()
+}
+
+// toy byteorder-abstracted readers: each decodes a byte buffer into the
+// u64 words it assumes were serialized with a particular endianness.
+#[cfg(test)]
+fn read_words_le(buf: &[u8]) -> Vec<u64> {
+    buf.chunks(8)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn read_words_be(buf: &[u8]) -> Vec<u64> {
+    buf.chunks(8)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u64::from_be_bytes(word)
+        })
+        .collect()
+}
+
+#[test]
+fn test_oneshot_matches_streamed() {
+    let data: Vec<u8> = (0..200u32).map(|i| i as u8).collect();
+    let whole = oneshot(&data, 7);
+
+    let mut state = StableXxHasher::new_with_seed(7);
+    for chunk in data.chunks(11) {
+        state.write(chunk);
+    }
+    assert_eq!(whole, state.finish());
+}
+
+#[test]
+fn test_usize_width_independent() {
+    // fits in 32 bits, so `as usize` keeps the value on every target.
+    let val = 0xdeadbeef_u64;
+    assert_eq!(hash(&val), hash(&(val as usize)));
+}
+
+#[test]
+fn test_cross_endian_reader_agrees() {
+    // `data` is a little-endian encoding of some u64 words; `swapped` is
+    // the big-endian encoding of the *same* words. Decoding each with the
+    // reader matching its own declared endianness must recover identical
+    // words, and feeding those words through `write_u64` must then
+    // produce identical digests - i.e. `StableXxHasher` really doesn't
+    // care which endianness the bytes on disk were written in, as long
+    // as the reader on the other end knows which one it's reading.
+    let data: Vec<u8> = (0..64u32).map(|i| (i * 7) as u8).collect();
+    let swapped = byte_swap_words(&data);
+    assert_eq!(swapped.len(), data.len());
+
+    let words_le = read_words_le(&data);
+    let words_be = read_words_be(&swapped);
+    assert_eq!(words_le, words_be);
+
+    let mut from_le = StableXxHasher::new_with_seed(0);
+    for word in &words_le {
+        from_le.write_u64(*word);
+    }
+
+    let mut from_be = StableXxHasher::new_with_seed(0);
+    for word in &words_be {
+        from_be.write_u64(*word);
+    }
+
+    assert_eq!(from_le.finish(), from_be.finish());
+}